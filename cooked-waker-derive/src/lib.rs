@@ -73,6 +73,75 @@ pub fn into_waker_derive(stream: pm::TokenStream) -> pm::TokenStream {
     implementation.into()
 }
 
+/// `IntoLocalWaker` derive implementation.
+///
+/// This mirrors [`into_waker_derive`] exactly, except that it builds a
+/// `core::task::LocalWaker` instead of a `Waker`, via `LocalWaker::from_raw`,
+/// and forwards to `LocalWake`/`WakeRef` instead of `Wake`/`WakeRef`. This
+/// lets single-threaded waker state (e.g. types built on `Rc`) skip the
+/// `Send + Sync` bound that `IntoWaker` requires.
+///
+/// Only available with the `local_waker` feature, since `LocalWaker` is
+/// currently nightly-only.
+#[proc_macro_derive(IntoLocalWaker)]
+pub fn into_local_waker_derive(stream: pm::TokenStream) -> pm::TokenStream {
+    let input = parse_macro_input!(stream as DeriveInput);
+
+    if !input.generics.params.is_empty() {
+        panic!("IntoLocalWaker can only be derived for concrete types");
+    }
+
+    #[allow(non_snake_case)]
+    let WakerStruct = input.ident;
+
+    let implementation = quote! {
+        impl cooked_waker::IntoLocalWaker for #WakerStruct {
+            #[must_use]
+            fn into_local_waker(self) -> core::task::LocalWaker {
+                use core::task::{LocalWaker, RawWaker, RawWakerVTable};
+                use core::clone::Clone;
+                use cooked_waker::{LocalWake, WakeRef};
+                use cooked_waker::stowaway::{self, Stowaway};
+
+                let stowed = Stowaway::new(self);
+
+                static VTABLE: RawWakerVTable = RawWakerVTable::new(
+                    // clone
+                    |raw| {
+                        let raw = raw as *mut ();
+                        let waker: & #WakerStruct = unsafe { stowaway::ref_from_stowed(&raw) };
+                        let cloned: #WakerStruct = Clone::clone(waker);
+                        let stowed_clone = stowaway::stow(cloned);
+                        RawWaker::new(stowed_clone, &VTABLE)
+                    },
+                    // wake by value
+                    |raw| {
+                        let waker: #WakerStruct = unsafe { stowaway::unstow(raw as *mut ()) };
+                        LocalWake::wake(waker);
+                    },
+                    // wake by ref
+                    |raw| {
+                        let raw = raw as *mut ();
+                        let waker: & #WakerStruct = unsafe { stowaway::ref_from_stowed(&raw) };
+                        WakeRef::wake_by_ref(waker)
+                    },
+                    // Drop
+                    |raw| {
+                        let _waker: Stowaway<#WakerStruct> = unsafe {
+                            Stowaway::from_raw(raw as *mut ())
+                        };
+                    },
+                );
+
+                let raw_waker = RawWaker::new(Stowaway::into_raw(stowed), &VTABLE);
+                unsafe { LocalWaker::from_raw(raw_waker) }
+            }
+        }
+    };
+
+    implementation.into()
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum WakeTrait {
     Wake,
@@ -179,26 +248,106 @@ fn derive_wake_like(spec: WakeTrait, stream: pm::TokenStream) -> pm::TokenStream
 
             implementation.into()
         }
-        Data::Enum(..) => unimplemented!("derive(Wake) for enums is still WIP"),
+        Data::Enum(data) => {
+            // Binding used inside each match arm: `inner` for Wake (which
+            // takes ownership of the matched variant), `ref inner` for
+            // WakeRef (which only borrows it).
+            let binding = match spec {
+                WakeTrait::Wake => quote! { inner },
+                WakeTrait::WakeRef => quote! { ref inner },
+            };
+
+            let mut match_arms = Vec::new();
+            let mut seen_field_types = Vec::new();
+
+            for variant in &data.variants {
+                let variant_ident = &variant.ident;
+
+                let fields = match &variant.fields {
+                    Fields::Named(fields) => fields.named.clone(),
+                    Fields::Unnamed(fields) => fields.unnamed.clone(),
+                    Fields::Unit => panic!(
+                        "`{name}` can only be derived on enums whose variants each have a \
+                         single `{name}` field",
+                        name = spec.name()
+                    ),
+                };
+
+                if fields.len() != 1 {
+                    panic!(
+                        "Can only derive `{name}` on enums whose variants each have exactly \
+                         1 field",
+                        name = spec.name()
+                    );
+                }
+
+                let field = fields.first().unwrap();
+                let field_type = &field.ty;
+
+                // Add a `where FieldType: Wake` predicate for each distinct
+                // field type used across the enum's variants.
+                let field_type_key = quote! {#field_type}.to_string();
+                if !seen_field_types.contains(&field_type_key) {
+                    seen_field_types.push(field_type_key);
+                    where_clause
+                        .predicates
+                        .push(parse_quote! {#field_type: #trait_path});
+                }
+
+                // The matched pattern: `Variant(ref inner)` for tuple
+                // variants, `Variant { field: ref inner }` for named ones.
+                let pattern = match &field.ident {
+                    Some(field_name) => quote! {#type_name::#variant_ident { #field_name: #binding } },
+                    None => quote! {#type_name::#variant_ident(#binding)},
+                };
+
+                // Unlike the struct case, `inner`'s reference-ness already
+                // comes from the match binding mode above (`inner` vs.
+                // `ref inner`), so it is used bare here -- applying
+                // `apply_reference` again would pass `&&FieldType` for
+                // `WakeRef`, dispatching through the `&T` forwarding impl
+                // rather than calling the field type's impl directly.
+                let field_invocation = quote! {inner};
+
+                match_arms.push(quote! {
+                    #pattern => #trait_path::#method(#field_invocation),
+                });
+            }
+
+            let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+            let self_param = spec.apply_reference(quote! {self});
+
+            let implementation = quote! {
+                impl #impl_generics #trait_path for #type_name #ty_generics #where_clause {
+                    #[inline]
+                    fn #method(#self_param) {
+                        match self {
+                            #(#match_arms)*
+                        }
+                    }
+                }
+            };
+
+            implementation.into()
+        }
         Data::Union(..) => panic!("`Wake` can only be derived for struct or enum types"),
     }
 }
 
-/// Create a `Wake` implementation for a `struct` that forwards to the
-/// `struct`'s field. The `struct` must have exactly one field, and that
-/// field must implement `Wake`.
-///
-/// In the future this derive will also support `enum`.
+/// Create a `Wake` implementation for a `struct` or `enum` that forwards to
+/// the relevant field. A `struct` must have exactly one field; an `enum`
+/// must have exactly one field in each of its variants. In both cases, every
+/// such field must implement `Wake`.
 #[proc_macro_derive(Wake)]
 pub fn wake_derive(stream: pm::TokenStream) -> pm::TokenStream {
     derive_wake_like(WakeTrait::Wake, stream)
 }
 
-/// Create a `WakeRef` implementation for a `struct` that forwards to the
-/// `struct`'s field. The `struct` must have exactly one field, and that
-/// field must implement `WakeRef`.
-///
-/// In the future this derive will also support `enum`.
+/// Create a `WakeRef` implementation for a `struct` or `enum` that forwards
+/// to the relevant field. A `struct` must have exactly one field; an `enum`
+/// must have exactly one field in each of its variants. In both cases, every
+/// such field must implement `WakeRef`.
 #[proc_macro_derive(WakeRef)]
 pub fn wake_ref_derive(stream: pm::TokenStream) -> pm::TokenStream {
     derive_wake_like(WakeTrait::WakeRef, stream)
@@ -1,5 +1,14 @@
 #![no_std]
+#![cfg_attr(feature = "local_waker", feature(local_waker))]
 
+//! **Note:** this crate predates the `ViaRawPointer`-based design in the
+//! workspace root crate, back from when generic types couldn't implement
+//! [`IntoWaker`] directly (see below) and a derive macro was the only way
+//! around that. The root crate's blanket impls cover the same ground
+//! without the derive macro or its `stowaway` dependency; prefer it for new
+//! code. This crate is kept working and is not being removed, since
+//! existing users depend on its derive-based API.
+//!
 //! cooked_waker provides safe traits for working with
 //! [`std::task::Waker`][Waker] and, more importantly, a set of derives for
 //! safely converting normal, safe rust types into `Waker` instances. It cooks
@@ -13,11 +22,16 @@
 //! that implements `Wake` or `WakeRef`
 //!
 //! Additionally, it provides [`IntoWaker`], which allows converting any
-//! `Wake + Clone` type into a [`Waker`]. Unfortunately, of limitations in
-//! how generics interact with static, it's not possible to implement this
-//! trait generically. We therefore instead provide a derive that can be
-//! applied to any *concrete* type; see the [`IntoWaker`] documentation for
-//! more information.
+//! `Wake + Clone` type into a [`Waker`]. For a generic type, such as `Arc<T>`,
+//! this requires sharing a single vtable-building function across every
+//! caller (see `arc_vtable` below); for a concrete type, a derive is provided
+//! that builds the equivalent impl without requiring you to write it by
+//! hand -- see the [`IntoWaker`] documentation for more information.
+//!
+//! With the `local_waker` feature enabled, this crate additionally provides
+//! [`LocalWake`] and [`IntoLocalWaker`], which mirror `Wake` and `IntoWaker`
+//! but drop the `Send + Sync` requirement, for building `core::task::LocalWaker`
+//! out of single-threaded waker state such as `Rc<T>`.
 //!
 //! # Basic example
 //!
@@ -138,6 +152,46 @@
 //!
 //! assert_eq!(counter.get(), 4);
 //! ```
+//!
+//! # Enum example
+//!
+//! `Wake`/`WakeRef` can also be derived for an enum, as long as each variant
+//! has exactly one field.
+//!
+//! ```
+//! use cooked_waker::WakeRef;
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//!
+//! static FIRST_COUNT: AtomicUsize = AtomicUsize::new(0);
+//! static SECOND_COUNT: AtomicUsize = AtomicUsize::new(0);
+//!
+//! struct First;
+//! impl WakeRef for First {
+//!     fn wake_by_ref(&self) {
+//!         FIRST_COUNT.fetch_add(1, Ordering::SeqCst);
+//!     }
+//! }
+//!
+//! struct Second;
+//! impl WakeRef for Second {
+//!     fn wake_by_ref(&self) {
+//!         SECOND_COUNT.fetch_add(1, Ordering::SeqCst);
+//!     }
+//! }
+//!
+//! #[derive(WakeRef)]
+//! enum EitherWaker {
+//!     First(First),
+//!     Second(Second),
+//! }
+//!
+//! EitherWaker::First(First).wake_by_ref();
+//! EitherWaker::Second(Second).wake_by_ref();
+//! EitherWaker::Second(Second).wake_by_ref();
+//!
+//! assert_eq!(FIRST_COUNT.load(Ordering::SeqCst), 1);
+//! assert_eq!(SECOND_COUNT.load(Ordering::SeqCst), 2);
+//! ```
 
 extern crate alloc;
 
@@ -152,7 +206,9 @@ pub use cooked_waker_derive::*;
 use alloc::boxed::Box;
 use alloc::rc;
 use alloc::sync as arc;
-use core::task::Waker;
+use core::mem::ManuallyDrop;
+use core::ops::Deref;
+use core::task::{RawWaker, RawWakerVTable, Waker};
 
 // Needed so that the derive macro can use it without requiring downstream
 // users to list it as a dependency
@@ -227,6 +283,425 @@ pub trait IntoWaker: Wake + Clone + Send + Sync + 'static {
     fn into_waker(self) -> Waker;
 }
 
+/// Wakers that can wake by value, for use with single-threaded executors.
+///
+/// This is the single-threaded counterpart to [`Wake`]: it has exactly the
+/// same shape, and it is what [`IntoLocalWaker`] builds on, which (unlike
+/// [`IntoWaker`]) doesn't require the implementing type (or its fields) to
+/// be thread-safe. This allows waker state like `Rc<T>` or `Cell` to be used
+/// directly, without wrapping it in `Arc` or atomics just to satisfy
+/// [`IntoWaker`].
+///
+/// Unlike [`Wake`], this trait has no derive.
+#[cfg(feature = "local_waker")]
+pub trait LocalWake: WakeRef + Sized {
+    /// Wake up the task by value. By default, this simply calls
+    /// [`WakeRef::wake_by_ref`].
+    ///
+    /// This function should be called by [`LocalWaker::wake`]; a derived
+    /// `IntoLocalWaker` implementation will set this up automatically.
+    ///
+    /// [`LocalWaker::wake`]: core::task::LocalWaker::wake
+    #[inline]
+    fn wake(self) {
+        self.wake_by_ref()
+    }
+}
+
+/// Objects that can be converted into a [`LocalWaker`]. You should usually
+/// be able to derive this trait for any concrete type that implements
+/// [`LocalWake + Clone + 'static`].
+///
+/// This is the `!Send + !Sync` counterpart to [`IntoWaker`], for use with
+/// single-threaded executors that poll futures with `core`'s (currently
+/// nightly-only) [`LocalWaker`] instead of [`Waker`].
+///
+/// Just like [`IntoWaker`], this trait can be derived for any *concrete*
+/// type; the derive sets up a [`RawWakerVTable`] for the type and arranges a
+/// conversion into a `LocalWaker` through the `stowaway` crate, exactly like
+/// the `IntoWaker` derive does.
+///
+/// ```
+/// #![feature(local_waker)]
+/// use cooked_waker::{LocalWake, WakeRef, IntoLocalWaker};
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// #[derive(Clone, IntoLocalWaker)]
+/// struct Counter(Rc<Cell<usize>>);
+///
+/// impl WakeRef for Counter {
+///     fn wake_by_ref(&self) {
+///         self.0.set(self.0.get() + 1);
+///     }
+/// }
+///
+/// impl LocalWake for Counter {}
+///
+/// let counter = Counter(Rc::new(Cell::new(0)));
+/// let waker = counter.clone().into_local_waker();
+/// waker.wake();
+/// assert_eq!(counter.0.get(), 1);
+/// ```
+///
+/// [`LocalWaker`]: core::task::LocalWaker
+/// [`RawWakerVTable`]: core::task::RawWakerVTable
+#[cfg(feature = "local_waker")]
+pub trait IntoLocalWaker: LocalWake + Clone + 'static {
+    /// Convert this object into a `LocalWaker`.
+    #[must_use]
+    fn into_local_waker(self) -> core::task::LocalWaker;
+}
+
+// Build the RawWakerVTable shared by the owned `Arc<T>: IntoWaker` impl
+// below and the borrowed `waker_ref` function. Reusing the exact same
+// vtable for both is what makes `Waker::will_wake` return true between a
+// borrowed waker and an owned one cloned from the same `Arc`.
+//
+// This is a free function, rather than an associated const, because `Arc<T>`
+// is a generic type and `IntoWaker` can otherwise only be derived for
+// concrete types. The vtable itself isn't bound to a named `static`: since
+// none of the closures below capture any environment, the call to
+// `RawWakerVTable::new` is a constant expression, so taking a `&` reference
+// to it triggers rvalue static promotion, giving each monomorphization of
+// `T` its own `'static` vtable without requiring callers to derive anything.
+fn arc_vtable<T>() -> &'static RawWakerVTable
+where
+    T: WakeRef + Wake + Send + Sync + 'static,
+{
+    &RawWakerVTable::new(
+        // clone: a real Arc::clone, bumping the refcount and returning an
+        // owned RawWaker.
+        |raw| {
+            let arc = unsafe { arc::Arc::<T>::from_raw(raw as *const T) };
+            let cloned = arc::Arc::clone(&arc);
+            let _ = arc::Arc::into_raw(arc);
+            RawWaker::new(arc::Arc::into_raw(cloned) as *const (), arc_vtable::<T>())
+        },
+        // wake by value
+        |raw| {
+            let arc = unsafe { arc::Arc::<T>::from_raw(raw as *const T) };
+            Wake::wake(arc);
+        },
+        // wake by ref
+        |raw| {
+            let arc = unsafe { arc::Arc::<T>::from_raw(raw as *const T) };
+            WakeRef::wake_by_ref(&arc);
+            let _ = arc::Arc::into_raw(arc);
+        },
+        // drop
+        |raw| {
+            let _arc = unsafe { arc::Arc::<T>::from_raw(raw as *const T) };
+        },
+    )
+}
+
+impl<T> IntoWaker for arc::Arc<T>
+where
+    T: WakeRef + Wake + Send + Sync + 'static,
+{
+    fn into_waker(self) -> Waker {
+        let raw = arc::Arc::into_raw(self) as *const ();
+        let raw_waker = RawWaker::new(raw, arc_vtable::<T>());
+        unsafe { Waker::from_raw(raw_waker) }
+    }
+}
+
+/// A borrowed [`Waker`], obtained from [`waker_ref`] without touching the
+/// refcount of the `Arc` it was built from.
+///
+/// Derefs to [`Waker`] so it can be used (almost) anywhere a `&Waker` is
+/// expected. When a `WakerRef` is dropped, its `Waker`'s drop glue is never
+/// run, so the `Arc` handle it borrowed from is left untouched.
+pub struct WakerRef<'a> {
+    waker: ManuallyDrop<Waker>,
+    _marker: core::marker::PhantomData<&'a ()>,
+}
+
+impl Deref for WakerRef<'_> {
+    type Target = Waker;
+
+    #[inline]
+    fn deref(&self) -> &Waker {
+        &self.waker
+    }
+}
+
+/// Construct a [`Waker`] that borrows its waker state from `wake`, rather
+/// than cloning it.
+///
+/// This lets an executor poll a future with a `Waker` backed by an `Arc`
+/// handle it already owns, without bumping the refcount on every poll. The
+/// returned [`WakerRef`] must not outlive the `Arc` it borrows from.
+///
+/// Because the vtable embedded in the returned `Waker` is the exact same
+/// `'static` vtable used by the owned `Arc<T>: IntoWaker` impl, a `Waker`
+/// cloned from this `WakerRef` is indistinguishable (via [`Waker::will_wake`])
+/// from one built by calling `wake.clone().into_waker()`.
+///
+/// ```
+/// use cooked_waker::{waker_ref, IntoWaker};
+/// use std::sync::Arc;
+/// use std::task::Waker;
+///
+/// #[derive(Clone)]
+/// struct MyWaker;
+///
+/// impl cooked_waker::WakeRef for MyWaker {
+///     fn wake_by_ref(&self) {}
+/// }
+///
+/// impl cooked_waker::Wake for MyWaker {}
+///
+/// let handle = Arc::new(MyWaker);
+/// let owned: Waker = handle.clone().into_waker();
+/// let borrowed: Waker = waker_ref(&handle).clone();
+///
+/// assert!(owned.will_wake(&borrowed));
+/// ```
+pub fn waker_ref<T>(wake: &arc::Arc<T>) -> WakerRef<'_>
+where
+    T: WakeRef + Wake + Send + Sync + 'static,
+{
+    let raw = arc::Arc::as_ptr(wake) as *const ();
+    let raw_waker = RawWaker::new(raw, arc_vtable::<T>());
+
+    WakerRef {
+        waker: ManuallyDrop::new(unsafe { Waker::from_raw(raw_waker) }),
+        _marker: core::marker::PhantomData,
+    }
+}
+
+/// A [`WakeRef`]/[`Wake`] implementation that forwards to a wrapped closure.
+///
+/// Constructed via [`from_ref_fn`]; see that function for details. `WakeFn`
+/// is generic over the closure type, so, unlike most concrete waker structs
+/// in this crate, it can't use `#[derive(IntoWaker)]` (that derive only
+/// supports concrete types); its `IntoWaker` impl below is hand-written to
+/// mirror exactly what that derive would generate, packing the closure into
+/// the pointer via `stowaway` (boxing it only if it's too large to fit) just
+/// like the derive does.
+#[cfg(feature = "derive")]
+#[derive(Clone)]
+pub struct WakeFn<F>(F);
+
+#[cfg(feature = "derive")]
+impl<F: Fn() + Clone> WakeRef for WakeFn<F> {
+    #[inline]
+    fn wake_by_ref(&self) {
+        (self.0)()
+    }
+}
+
+#[cfg(feature = "derive")]
+impl<F: Fn() + Clone> Wake for WakeFn<F> {
+    #[inline]
+    fn wake(self) {
+        (self.0)()
+    }
+}
+
+#[cfg(feature = "derive")]
+fn wake_fn_vtable<F>() -> &'static RawWakerVTable
+where
+    F: Fn() + Clone + Send + Sync + 'static,
+{
+    &RawWakerVTable::new(
+        // clone
+        |raw| {
+            let raw = raw as *mut ();
+            let waker: &WakeFn<F> = unsafe { stowaway::ref_from_stowed(&raw) };
+            let cloned: WakeFn<F> = Clone::clone(waker);
+            let stowed_clone = stowaway::stow(cloned);
+            RawWaker::new(stowed_clone, wake_fn_vtable::<F>())
+        },
+        // wake by value
+        |raw| {
+            let waker: WakeFn<F> = unsafe { stowaway::unstow(raw as *mut ()) };
+            Wake::wake(waker);
+        },
+        // wake by ref
+        |raw| {
+            let raw = raw as *mut ();
+            let waker: &WakeFn<F> = unsafe { stowaway::ref_from_stowed(&raw) };
+            WakeRef::wake_by_ref(waker);
+        },
+        // drop
+        |raw| {
+            let _waker: stowaway::Stowaway<WakeFn<F>> =
+                unsafe { stowaway::Stowaway::from_raw(raw as *mut ()) };
+        },
+    )
+}
+
+#[cfg(feature = "derive")]
+impl<F> IntoWaker for WakeFn<F>
+where
+    F: Fn() + Clone + Send + Sync + 'static,
+{
+    fn into_waker(self) -> Waker {
+        let stowed = stowaway::stow(self);
+        let raw_waker = RawWaker::new(stowed, wake_fn_vtable::<F>());
+        unsafe { Waker::from_raw(raw_waker) }
+    }
+}
+
+/// Build a [`Waker`] directly from a closure, without declaring a dedicated
+/// struct and hand-writing `WakeRef`/`Wake`/`IntoWaker` for it.
+///
+/// The closure is called by both [`WakeRef::wake_by_ref`] and [`Wake::wake`],
+/// so it should be cheap to call repeatedly; see [`from_fn`] for a
+/// by-value-oriented alternative that calls its closure at most once.
+///
+/// ```
+/// use cooked_waker::{from_ref_fn, IntoWaker};
+///
+/// let waker = from_ref_fn(|| println!("woken!")).into_waker();
+/// waker.wake_by_ref();
+/// ```
+#[cfg(feature = "derive")]
+pub fn from_ref_fn<F>(f: F) -> WakeFn<F>
+where
+    F: Fn() + Clone,
+{
+    WakeFn(f)
+}
+
+/// A [`WakeRef`]/[`Wake`] implementation that calls its wrapped closure at
+/// most once. Constructed via [`from_fn`].
+///
+/// Unlike [`WakeFn`], the closure here is `FnOnce` rather than `Fn + Clone`,
+/// so this can wrap state (e.g. a oneshot sender) that can't be cloned or
+/// called more than once. It doesn't implement [`IntoWaker`], since that
+/// trait assumes a [`Waker`] may be cloned and woken an unbounded number of
+/// times; use [`from_ref_fn`]/[`WakeFn`] if you need an actual `Waker`.
+///
+/// Because [`WakeRef::wake_by_ref`] only takes `&self`, the closure is
+/// stored behind a [`Cell`](core::cell::Cell), so whichever of [`WakeRef::wake_by_ref`] or
+/// [`Wake::wake`] runs first takes and calls it; later calls are no-ops.
+pub struct WakeFnOnce<F>(core::cell::Cell<Option<F>>);
+
+impl<F: FnOnce()> WakeRef for WakeFnOnce<F> {
+    fn wake_by_ref(&self) {
+        if let Some(f) = self.0.take() {
+            f();
+        }
+    }
+}
+
+impl<F: FnOnce()> Wake for WakeFnOnce<F> {}
+
+/// Build a one-shot waker directly from an `FnOnce` closure, for state that
+/// can't be cloned or called more than once (unlike [`from_ref_fn`], which
+/// requires `Fn + Clone`).
+///
+/// ```
+/// use cooked_waker::{from_fn, Wake};
+///
+/// let (tx, rx) = std::sync::mpsc::channel();
+/// let waker = from_fn(move || tx.send(()).unwrap());
+/// waker.wake();
+/// assert_eq!(rx.recv(), Ok(()));
+/// ```
+pub fn from_fn<F>(f: F) -> WakeFnOnce<F>
+where
+    F: FnOnce() + Send + 'static,
+{
+    WakeFnOnce(core::cell::Cell::new(Some(f)))
+}
+
+/// Owned storage for a [`core::task::Context`] assembled from cooked
+/// wakers.
+///
+/// `Context::from_waker` (and the nightly `ContextBuilder`) only *borrow*
+/// their waker(s), so something needs to own the `Waker` (and, with the
+/// `local_waker` feature, the `LocalWaker`) for as long as the `Context`
+/// built from them is in use. `IntoContext` is that storage: build one with
+/// [`IntoContext::new`], optionally attach a thread-local waker with
+/// [`IntoContext::with_local_waker`], then call [`IntoContext::context`]
+/// each time a `Context` is needed to poll a future.
+pub struct IntoContext {
+    waker: Waker,
+    #[cfg(feature = "local_waker")]
+    local_waker: Option<core::task::LocalWaker>,
+}
+
+impl IntoContext {
+    /// Convert a `Wake`-implementing handle into owned `Context` storage.
+    #[must_use]
+    pub fn new<W>(wake: W) -> Self
+    where
+        W: IntoWaker,
+    {
+        IntoContext {
+            waker: wake.into_waker(),
+            #[cfg(feature = "local_waker")]
+            local_waker: None,
+        }
+    }
+
+    /// Attach a thread-local waker, converted via [`IntoLocalWaker`], to be
+    /// exposed through the built `Context`'s `local_waker` slot.
+    #[cfg(feature = "local_waker")]
+    #[must_use]
+    pub fn with_local_waker<L>(mut self, wake: L) -> Self
+    where
+        L: IntoLocalWaker,
+    {
+        self.local_waker = Some(wake.into_local_waker());
+        self
+    }
+
+    /// Build a [`core::task::Context`] borrowing from this storage.
+    #[must_use]
+    pub fn context(&self) -> core::task::Context<'_> {
+        #[cfg(feature = "local_waker")]
+        {
+            let mut builder = core::task::ContextBuilder::from_waker(&self.waker);
+            if let Some(local_waker) = &self.local_waker {
+                builder = builder.local_waker(local_waker);
+            }
+            builder.build()
+        }
+
+        #[cfg(not(feature = "local_waker"))]
+        {
+            core::task::Context::from_waker(&self.waker)
+        }
+    }
+}
+
+/// Convenience free-function alias for [`IntoContext::new`].
+///
+/// ```
+/// use cooked_waker::{context_builder, Wake, WakeRef, IntoWaker};
+/// use std::future::Future;
+/// use std::pin::Pin;
+/// use std::task::Poll;
+///
+/// #[derive(Debug, Clone, IntoWaker)]
+/// struct NoopWaker;
+///
+/// impl WakeRef for NoopWaker {
+///     fn wake_by_ref(&self) {}
+/// }
+///
+/// impl Wake for NoopWaker {}
+///
+/// let storage = context_builder(NoopWaker);
+/// let mut cx = storage.context();
+///
+/// let mut fut = Box::pin(async { 1 + 1 });
+/// assert_eq!(fut.as_mut().poll(&mut cx), Poll::Ready(2));
+/// ```
+#[must_use]
+pub fn context_builder<W>(wake: W) -> IntoContext
+where
+    W: IntoWaker,
+{
+    IntoContext::new(wake)
+}
+
 // Waker implementations for std types.
 impl<T: WakeRef> WakeRef for &T {
     #[inline]
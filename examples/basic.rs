@@ -34,6 +34,10 @@ unsafe impl ViaRawPointer for CustomWaker {
     unsafe fn from_raw(ptr: *mut Self::Target) -> Self {
         Self { id: ptr as i32 }
     }
+
+    fn as_raw(&self) -> *mut Self::Target {
+        self.id as *mut ()
+    }
 }
 
 fn main() {
@@ -1,4 +1,5 @@
 #![no_std]
+#![cfg_attr(feature = "local_waker", feature(local_waker))]
 
 //! cooked_waker provides safe traits for working with
 //! [`std::task::Waker`][Waker] and creating those wakers out of regular, safe
@@ -15,6 +16,11 @@
 //! `Wake + Clone` type into a [`Waker`]. This trait is automatically derived
 //! for any `Wake + Clone + Send + Sync + 'static` type.
 //!
+//! With the `local_waker` feature enabled, it additionally provides
+//! [`LocalWake`] and [`IntoLocalWaker`], the `!Send + !Sync` counterparts of
+//! [`Wake`] and [`IntoWaker`], for building `core::task::LocalWaker` out of
+//! single-threaded waker state such as `Rc<T>`.
+//!
 //! # Basic example
 //!
 //! ```
@@ -64,6 +70,10 @@
 //!     unsafe fn from_raw(ptr: *mut ()) -> Self {
 //!         StaticWaker
 //!     }
+//!
+//!     fn as_raw(&self) -> *mut () {
+//!         std::ptr::null_mut()
+//!     }
 //! }
 //!
 //! assert_eq!(drop_count.load(Ordering::SeqCst), 0);
@@ -148,6 +158,9 @@ use core::{
     task::{RawWaker, RawWakerVTable, Waker},
 };
 
+#[cfg(feature = "local_waker")]
+use core::task::LocalWaker;
+
 /// Trait for types that can be converted into raw pointers and back again.
 /// Implementors must ensure that, for a given object, the pointer remains
 /// fixed as long as no mutable operations are performed (that is, calling
@@ -166,6 +179,13 @@ pub trait ViaRawPointer {
     /// called on a pointer that was received via `Self::into_raw`, and that
     /// pointer must not be used afterwards.
     unsafe fn from_raw(ptr: *mut Self::Target) -> Self;
+
+    /// Peek at the raw pointer this object would produce via [`into_raw`],
+    /// without consuming it. Implementors must ensure this returns the same
+    /// pointer value that a subsequent call to `into_raw` would.
+    ///
+    /// [`into_raw`]: Self::into_raw
+    fn as_raw(&self) -> *mut Self::Target;
 }
 
 /// Wakers that can wake by reference. This trait is used to enable a [`Wake`]
@@ -223,23 +243,28 @@ pub trait Wake: WakeRef + Sized {
 /// [`Waker`]: core::task::Waker
 /// [`Clone`]: core::clone::Clone
 pub trait IntoWaker {
-    /// The RawWakerVTable for this type. This should never be used directly;
-    /// it is entirely handled by `into_waker`. It is present as an associated
-    /// const because that's the only way for it to work in generic contexts.
-    #[doc(hidden)]
-    const VTABLE: &'static RawWakerVTable;
-
     /// Convert this object into a `Waker`.
     #[must_use]
     fn into_waker(self) -> Waker;
 }
 
-impl<T> IntoWaker for T
+// The RawWakerVTable shared by `IntoWaker::into_waker` and `waker_ref` for a
+// given `T`. This is a plain function, rather than an associated const on
+// `IntoWaker`, because a `const` read from multiple source locations isn't
+// guaranteed to resolve to the same promoted `'static` value at each one
+// (only LLVM's constant-merging, an optimization and not a language
+// guarantee, happens to make that work out in release builds). A function
+// doesn't have this problem: its body, including the rvalue-promoted
+// `RawWakerVTable`, is compiled once per monomorphization of `T`, so every
+// caller of `waker_vtable::<T>()` — including this function's own clone
+// closure below — observes the exact same address, which is what makes
+// `Waker::will_wake` behave correctly.
+fn waker_vtable<T>() -> &'static RawWakerVTable
 where
     T: Wake + Clone + Send + Sync + 'static + ViaRawPointer,
     T::Target: Sized,
 {
-    const VTABLE: &'static RawWakerVTable = &RawWakerVTable::new(
+    &RawWakerVTable::new(
         // clone
         |raw| {
             let raw = raw as *mut T::Target;
@@ -256,7 +281,7 @@ where
 
             let cloned_raw = cloned.into_raw();
             let cloned_raw = cloned_raw as *const ();
-            RawWaker::new(cloned_raw, T::VTABLE)
+            RawWaker::new(cloned_raw, waker_vtable::<T>())
         },
         // wake by value
         |raw| {
@@ -278,16 +303,465 @@ where
             let raw = raw as *mut T::Target;
             let _waker: T = unsafe { ViaRawPointer::from_raw(raw) };
         },
-    );
+    )
+}
 
+impl<T> IntoWaker for T
+where
+    T: Wake + Clone + Send + Sync + 'static + ViaRawPointer,
+    T::Target: Sized,
+{
     fn into_waker(self) -> Waker {
         let raw = self.into_raw();
         let raw = raw as *const ();
-        let raw_waker = RawWaker::new(raw, T::VTABLE);
+        let raw_waker = RawWaker::new(raw, waker_vtable::<T>());
         unsafe { Waker::from_raw(raw_waker) }
     }
 }
 
+/// A borrowed [`Waker`], obtained from [`waker_ref`] without performing the
+/// ownership transfer (e.g. an `Arc` refcount bump) that
+/// [`IntoWaker::into_waker`] would require.
+///
+/// `WakerRef` derefs to [`Waker`], so it can be used (almost) anywhere a
+/// `&Waker` is expected. Its `Drop` glue never runs the wrapped `Waker`'s
+/// drop function, so the handle it was borrowed from is left untouched.
+pub struct WakerRef<'a> {
+    waker: core::mem::ManuallyDrop<Waker>,
+    _marker: core::marker::PhantomData<&'a ()>,
+}
+
+impl core::ops::Deref for WakerRef<'_> {
+    type Target = Waker;
+
+    #[inline]
+    fn deref(&self) -> &Waker {
+        &self.waker
+    }
+}
+
+/// Construct a [`Waker`] that borrows its waker state from `handle`, rather
+/// than cloning it.
+///
+/// This lets an executor poll a future with a `Waker` backed by a handle
+/// (e.g. an `Arc<T>`) it already owns, without paying for a clone on every
+/// poll. The returned [`WakerRef`] must not outlive `handle`.
+///
+/// Because the vtable embedded in the returned `Waker` is the exact same
+/// `'static` vtable ([`waker_vtable::<T>`](waker_vtable)) used by `T`'s
+/// [`IntoWaker`] impl, a `Waker` cloned from this `WakerRef` is
+/// indistinguishable, via [`Waker::will_wake`], from one built by calling
+/// `handle.clone().into_waker()`.
+///
+/// ```
+/// use cooked_waker::{waker_ref, IntoWaker};
+/// use std::sync::Arc;
+/// use std::task::Waker;
+///
+/// #[derive(Clone)]
+/// struct MyWaker;
+///
+/// impl cooked_waker::WakeRef for MyWaker {
+///     fn wake_by_ref(&self) {}
+/// }
+///
+/// impl cooked_waker::Wake for MyWaker {}
+///
+/// let handle = Arc::new(MyWaker);
+/// let owned: Waker = handle.clone().into_waker();
+/// let borrowed: Waker = waker_ref(&handle).clone();
+///
+/// assert!(owned.will_wake(&borrowed));
+/// ```
+pub fn waker_ref<T>(handle: &T) -> WakerRef<'_>
+where
+    T: Wake + Clone + Send + Sync + 'static + ViaRawPointer,
+    T::Target: Sized,
+{
+    let raw = handle.as_raw() as *const ();
+    let raw_waker = RawWaker::new(raw, waker_vtable::<T>());
+
+    WakerRef {
+        waker: core::mem::ManuallyDrop::new(unsafe { Waker::from_raw(raw_waker) }),
+        _marker: core::marker::PhantomData,
+    }
+}
+
+/// A [`WakeRef`]/[`Wake`] implementation that calls a wrapped closure on
+/// every wake. Constructed via [`wake_fn_ref`].
+///
+/// Since a closure generally isn't pointer-sized, `WakeFn` boxes it and
+/// reuses the existing `Box<T>: ViaRawPointer` impl for the `into_raw`/
+/// `from_raw`/`as_raw` round-trip that [`IntoWaker`] needs.
+pub struct WakeFn<F>(Box<F>);
+
+impl<F: Clone> Clone for WakeFn<F> {
+    #[inline]
+    fn clone(&self) -> Self {
+        WakeFn(self.0.clone())
+    }
+}
+
+impl<F: Fn()> WakeRef for WakeFn<F> {
+    #[inline]
+    fn wake_by_ref(&self) {
+        (self.0)()
+    }
+}
+
+impl<F: Fn()> Wake for WakeFn<F> {
+    #[inline]
+    fn wake(self) {
+        (self.0)()
+    }
+}
+
+impl<F> ViaRawPointer for WakeFn<F> {
+    type Target = F;
+
+    fn into_raw(self) -> *mut F {
+        self.0.into_raw()
+    }
+
+    unsafe fn from_raw(ptr: *mut F) -> Self {
+        WakeFn(unsafe { Box::from_raw(ptr) })
+    }
+
+    fn as_raw(&self) -> *mut F {
+        self.0.as_raw()
+    }
+}
+
+/// Build a type implementing [`WakeRef`]/[`Wake`]/[`IntoWaker`] directly from
+/// a closure, so that `wake_fn_ref(|| { ... }).into_waker()` just works
+/// without declaring a dedicated struct and hand-writing its trait impls.
+///
+/// The closure is called on every [`Waker::wake_by_ref`] and [`Waker::wake`],
+/// so it should be cheap to call repeatedly; see [`wake_fn`] for a
+/// by-value-oriented alternative that calls its closure at most once.
+pub fn wake_fn_ref<F>(f: F) -> WakeFn<F>
+where
+    F: Fn() + Clone + Send + Sync + 'static,
+{
+    WakeFn(Box::new(f))
+}
+
+/// A [`WakeRef`]/[`Wake`] implementation that calls its wrapped closure at
+/// most once. Constructed via [`wake_fn`].
+///
+/// Unlike [`WakeFn`], the closure here is `FnOnce` rather than `Fn + Clone`,
+/// so this can wrap state (e.g. a oneshot sender) that can't be cloned or
+/// called more than once. It doesn't implement [`IntoWaker`], since that
+/// trait assumes a [`Waker`] may be cloned and woken an unbounded number of
+/// times; use [`wake_fn_ref`]/[`WakeFn`] if you need an actual `Waker`.
+///
+/// Because [`WakeRef::wake_by_ref`] only takes `&self`, the closure is
+/// stored behind a [`core::cell::Cell`], so whichever of
+/// [`WakeRef::wake_by_ref`] or [`Wake::wake`] runs first takes and calls it;
+/// later calls are no-ops.
+pub struct WakeFnOnce<F>(core::cell::Cell<Option<F>>);
+
+impl<F: FnOnce()> WakeRef for WakeFnOnce<F> {
+    fn wake_by_ref(&self) {
+        if let Some(f) = self.0.take() {
+            f();
+        }
+    }
+}
+
+impl<F: FnOnce()> Wake for WakeFnOnce<F> {}
+
+/// Build a one-shot waker directly from an `FnOnce` closure, for state that
+/// can't be cloned or called more than once (unlike [`wake_fn_ref`], which
+/// requires `Fn + Clone`).
+///
+/// ```
+/// use cooked_waker::{wake_fn, Wake};
+///
+/// let (tx, rx) = std::sync::mpsc::channel();
+/// let waker = wake_fn(move || tx.send(()).unwrap());
+/// waker.wake();
+/// assert_eq!(rx.recv(), Ok(()));
+/// ```
+pub fn wake_fn<F>(f: F) -> WakeFnOnce<F>
+where
+    F: FnOnce() + Send + 'static,
+{
+    WakeFnOnce(core::cell::Cell::new(Some(f)))
+}
+
+/// Wakers that can wake by value, for use with single-threaded executors.
+///
+/// This is the single-threaded counterpart to [`Wake`]: it has the exact
+/// same shape, and [`IntoLocalWaker`]'s blanket impl is bounded on it
+/// rather than on [`Wake`]. Unlike [`IntoWaker`]'s blanket impl, that impl
+/// doesn't require `Send + Sync`.
+///
+/// Only available with the `local_waker` feature, since
+/// [`core::task::LocalWaker`] is currently nightly-only.
+#[cfg(feature = "local_waker")]
+pub trait LocalWake: WakeRef + Sized {
+    /// Wake up the task by value. By default, this simply calls
+    /// [`WakeRef::wake_by_ref`].
+    ///
+    /// A [`LocalWaker`] created by [`IntoLocalWaker`] will call this method
+    /// through [`LocalWaker::wake`].
+    #[inline]
+    fn wake(self) {
+        self.wake_by_ref()
+    }
+}
+
+/// Objects that can be converted into a [`LocalWaker`]. This is the
+/// `!Send + !Sync` counterpart to [`IntoWaker`], for building a
+/// [`LocalWaker`] out of waker state that isn't thread-safe, like `Rc<T>`.
+///
+/// This trait is automatically implemented for types that fulfill the
+/// local-waker interface; see [`IntoWaker`] for the equivalent `Send + Sync`
+/// case, whose blanket impl this one mirrors exactly, down to the
+/// [`ViaRawPointer`]-based vtable construction. It uses its own vtable,
+/// distinct from `IntoWaker`'s, so that `LocalWaker::will_wake` behaves
+/// correctly for types that implement both traits.
+///
+/// Only available with the `local_waker` feature.
+///
+/// ```
+/// #![feature(local_waker)]
+/// use cooked_waker::{IntoLocalWaker, LocalWake, ViaRawPointer, WakeRef};
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// #[derive(Clone)]
+/// struct Counter(Rc<Cell<usize>>);
+///
+/// impl WakeRef for Counter {
+///     fn wake_by_ref(&self) {
+///         self.0.set(self.0.get() + 1);
+///     }
+/// }
+///
+/// impl LocalWake for Counter {}
+///
+/// impl ViaRawPointer for Counter {
+///     type Target = Cell<usize>;
+///
+///     fn into_raw(self) -> *mut Cell<usize> {
+///         Rc::into_raw(self.0) as *mut Cell<usize>
+///     }
+///
+///     unsafe fn from_raw(ptr: *mut Cell<usize>) -> Self {
+///         Counter(Rc::from_raw(ptr as *const Cell<usize>))
+///     }
+///
+///     fn as_raw(&self) -> *mut Cell<usize> {
+///         Rc::as_ptr(&self.0) as *mut Cell<usize>
+///     }
+/// }
+///
+/// let counter = Counter(Rc::new(Cell::new(0)));
+/// let waker = counter.clone().into_local_waker();
+/// waker.wake();
+/// assert_eq!(counter.0.get(), 1);
+/// ```
+///
+/// [`LocalWaker`]: core::task::LocalWaker
+/// [`LocalWaker::wake`]: core::task::LocalWaker::wake
+#[cfg(feature = "local_waker")]
+pub trait IntoLocalWaker {
+    /// Convert this object into a `LocalWaker`.
+    #[must_use]
+    fn into_local_waker(self) -> LocalWaker;
+}
+
+// The RawWakerVTable shared by `IntoLocalWaker::into_local_waker`, distinct
+// from `waker_vtable` so that `will_wake` behaves correctly for types
+// implementing both traits. See `waker_vtable`'s doc comment for why this is
+// a plain function rather than an associated const.
+#[cfg(feature = "local_waker")]
+fn local_waker_vtable<T>() -> &'static RawWakerVTable
+where
+    T: LocalWake + Clone + 'static + ViaRawPointer,
+    T::Target: Sized,
+{
+    &RawWakerVTable::new(
+        // clone
+        |raw| {
+            let raw = raw as *mut T::Target;
+
+            let waker: T = unsafe { ViaRawPointer::from_raw(raw) };
+            let cloned = waker.clone();
+
+            // See the equivalent comment in the IntoWaker impl above.
+            let waker_raw = waker.into_raw();
+            debug_assert_eq!(waker_raw, raw);
+
+            let cloned_raw = cloned.into_raw();
+            let cloned_raw = cloned_raw as *const ();
+            RawWaker::new(cloned_raw, local_waker_vtable::<T>())
+        },
+        // wake by value
+        |raw| {
+            let raw = raw as *mut T::Target;
+            let waker: T = unsafe { ViaRawPointer::from_raw(raw) };
+            LocalWake::wake(waker);
+        },
+        // wake by ref
+        |raw| {
+            let raw = raw as *mut T::Target;
+            let waker: T = unsafe { ViaRawPointer::from_raw(raw) };
+            waker.wake_by_ref();
+
+            let waker_raw = waker.into_raw();
+            debug_assert_eq!(waker_raw, raw);
+        },
+        // Drop
+        |raw| {
+            let raw = raw as *mut T::Target;
+            let _waker: T = unsafe { ViaRawPointer::from_raw(raw) };
+        },
+    )
+}
+
+#[cfg(feature = "local_waker")]
+impl<T> IntoLocalWaker for T
+where
+    T: LocalWake + Clone + 'static + ViaRawPointer,
+    T::Target: Sized,
+{
+    fn into_local_waker(self) -> LocalWaker {
+        let raw = self.into_raw();
+        let raw = raw as *const ();
+        let raw_waker = RawWaker::new(raw, local_waker_vtable::<T>());
+        unsafe { LocalWaker::from_raw(raw_waker) }
+    }
+}
+
+/// Owned storage for the waker(s) that a [`core::task::Context`] built by
+/// [`context_from`] borrows from.
+///
+/// `Context::from_waker` (and the nightly `ContextBuilder`) only *borrow*
+/// their waker(s), so something has to own the converted `Waker` (and, with
+/// the `local_waker` feature, `LocalWaker`) for as long as the `Context`
+/// built from them is in use. `ContextStorage` is that something.
+#[derive(Default)]
+pub struct ContextStorage {
+    waker: Option<Waker>,
+    #[cfg(feature = "local_waker")]
+    local_waker: Option<LocalWaker>,
+}
+
+impl ContextStorage {
+    /// Create empty storage. Call [`context_from`] to populate it and obtain
+    /// a `Context`.
+    #[must_use]
+    pub const fn new() -> Self {
+        ContextStorage {
+            waker: None,
+            #[cfg(feature = "local_waker")]
+            local_waker: None,
+        }
+    }
+}
+
+/// Convert `handle` into a [`Waker`], store it in `storage`, and build a
+/// [`core::task::Context`] borrowing from that storage.
+///
+/// This saves callers from manually calling [`IntoWaker::into_waker`],
+/// stashing the result in a local, and feeding it to
+/// [`core::task::Context::from_waker`] on every poll.
+///
+/// ```
+/// use cooked_waker::{context_from, ContextStorage, Wake, WakeRef, ViaRawPointer};
+/// use std::future::Future;
+/// use std::pin::Pin;
+/// use std::task::Poll;
+///
+/// #[derive(Clone)]
+/// struct NoopWaker;
+///
+/// impl WakeRef for NoopWaker {
+///     fn wake_by_ref(&self) {}
+/// }
+///
+/// impl Wake for NoopWaker {}
+///
+/// impl ViaRawPointer for NoopWaker {
+///     type Target = ();
+///
+///     fn into_raw(self) -> *mut () {
+///         std::ptr::null_mut()
+///     }
+///
+///     unsafe fn from_raw(_ptr: *mut ()) -> Self {
+///         NoopWaker
+///     }
+///
+///     fn as_raw(&self) -> *mut () {
+///         std::ptr::null_mut()
+///     }
+/// }
+///
+/// let mut storage = ContextStorage::new();
+/// let mut cx = context_from(&mut storage, NoopWaker);
+///
+/// let mut fut = Box::pin(async { 1 + 1 });
+/// assert_eq!(fut.as_mut().poll(&mut cx), Poll::Ready(2));
+/// ```
+pub fn context_from<W>(storage: &mut ContextStorage, handle: W) -> core::task::Context<'_>
+where
+    W: IntoWaker,
+{
+    storage.waker = Some(handle.into_waker());
+
+    // `context_from_with_local` may have left a local waker in this storage
+    // from an earlier poll; a plain `context_from` call means "no local
+    // waker", so clear it rather than silently inheriting one.
+    #[cfg(feature = "local_waker")]
+    {
+        storage.local_waker = None;
+    }
+
+    build_context(storage)
+}
+
+/// Like [`context_from`], but also converts `local` into a [`LocalWaker`]
+/// via [`IntoLocalWaker::into_local_waker`] and exposes it through the
+/// built `Context`'s `local_waker` slot.
+#[cfg(feature = "local_waker")]
+pub fn context_from_with_local<W, L>(
+    storage: &mut ContextStorage,
+    handle: W,
+    local: L,
+) -> core::task::Context<'_>
+where
+    W: IntoWaker,
+    L: IntoLocalWaker,
+{
+    storage.waker = Some(handle.into_waker());
+    storage.local_waker = Some(local.into_local_waker());
+    build_context(storage)
+}
+
+/// Build a `Context` from whatever is currently stashed in `storage`.
+///
+/// Shared by [`context_from`] and [`context_from_with_local`] once they've
+/// each finished populating `storage` the way they need to.
+fn build_context(storage: &mut ContextStorage) -> core::task::Context<'_> {
+    #[cfg(feature = "local_waker")]
+    {
+        let mut builder = core::task::ContextBuilder::from_waker(storage.waker.as_ref().unwrap());
+        if let Some(local_waker) = &storage.local_waker {
+            builder = builder.local_waker(local_waker);
+        }
+        builder.build()
+    }
+
+    #[cfg(not(feature = "local_waker"))]
+    {
+        core::task::Context::from_waker(storage.waker.as_ref().unwrap())
+    }
+}
+
 // Waker implementations for std types. Feel free to open PRs for additional
 // stdlib types here.
 
@@ -313,6 +787,10 @@ impl<T: ?Sized> ViaRawPointer for Box<T> {
     unsafe fn from_raw(ptr: *mut T) -> Self {
         Box::from_raw(ptr)
     }
+
+    fn as_raw(&self) -> *mut T {
+        &**self as *const T as *mut T
+    }
 }
 
 impl<T: WakeRef + ?Sized> WakeRef for Box<T> {
@@ -339,6 +817,10 @@ impl<T: ?Sized> ViaRawPointer for arc::Arc<T> {
     unsafe fn from_raw(ptr: *mut T) -> Self {
         arc::Arc::from_raw(ptr as *const T)
     }
+
+    fn as_raw(&self) -> *mut T {
+        arc::Arc::as_ptr(self) as *mut T
+    }
 }
 
 impl<T: WakeRef + ?Sized> WakeRef for arc::Arc<T> {
@@ -360,6 +842,10 @@ impl<T> ViaRawPointer for arc::Weak<T> {
     unsafe fn from_raw(ptr: *mut T) -> Self {
         arc::Weak::from_raw(ptr as *const T)
     }
+
+    fn as_raw(&self) -> *mut T {
+        arc::Weak::as_ptr(self) as *mut T
+    }
 }
 
 impl<T: WakeRef + ?Sized> WakeRef for arc::Weak<T> {
@@ -388,6 +874,10 @@ impl<T: ?Sized> ViaRawPointer for rc::Rc<T> {
     unsafe fn from_raw(ptr: *mut T) -> Self {
         rc::Rc::from_raw(ptr as *const T)
     }
+
+    fn as_raw(&self) -> *mut T {
+        rc::Rc::as_ptr(self) as *mut T
+    }
 }
 
 impl<T: WakeRef + ?Sized> Wake for rc::Rc<T> {
@@ -407,6 +897,10 @@ impl<T> ViaRawPointer for rc::Weak<T> {
     unsafe fn from_raw(ptr: *mut T) -> Self {
         rc::Weak::from_raw(ptr as *const T)
     }
+
+    fn as_raw(&self) -> *mut T {
+        rc::Weak::as_ptr(self) as *mut T
+    }
 }
 
 impl<T: WakeRef + ?Sized> WakeRef for rc::Weak<T> {
@@ -437,6 +931,13 @@ where
             true => None,
         }
     }
+
+    fn as_raw(&self) -> *mut Self::Target {
+        match self {
+            Some(value) => value.as_raw(),
+            None => ptr::null_mut(),
+        }
+    }
 }
 
 impl<T: WakeRef> WakeRef for Option<T> {
@@ -517,6 +1018,10 @@ mod test {
         unsafe fn from_raw(_ptr: *mut ()) -> Self {
             PanicWaker
         }
+
+        fn as_raw(&self) -> *mut () {
+            std::ptr::null_mut()
+        }
     }
 
     // Test that the wake_by_ref() behaves correctly even if it panics.